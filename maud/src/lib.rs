@@ -152,6 +152,47 @@
 //!
 //! As with literals, expression values are escaped by default. Use a
 //! `$$` prefix to disable this behavior.
+//!
+//! Pass a format spec after a semicolon to control formatting:
+//! `$(value; #x)` produces `0x2a`, and `$(ratio; .2)` prints two
+//! decimal places. The spec is the part you would write after the `:`
+//! in a `{:...}` placeholder.
+//!
+//! Use the `$[expr]` form to format a value with `{:?}` instead of
+//! `{}`, handy for debugging or for types that only implement `Debug`.
+//! It escapes by default too, and honors the `$$[expr]` unescaped
+//! variant.
+//!
+//! ## Control flow
+//!
+//! ```
+//! enum Status { Ok, Down }
+//! let numbers = [1, 2, 3];
+//! let logged_in = true;
+//! let status = Status::Ok;
+//! html! {
+//!     ul {
+//!         @for n in &numbers { li { $n } }
+//!     }
+//!     @if logged_in {
+//!         "Welcome back!"
+//!     } @else {
+//!         "Please sign in."
+//!     }
+//!     @match status {
+//!         Status::Ok => "ok",
+//!         _ => "fail",
+//!     }
+//! }
+//! ```
+//!
+//! Prefix a Rust control-flow keyword with `@` to use it inside a
+//! template: `@if`/`@else`, `@for`, and `@match`. The block bodies use
+//! the same Maud markup syntax as everywhere else, and `@match` arms
+//! take a markup block (or a single node) on the right of each `=>`.
+//!
+//! Bind a local with `@let name = expr;`; it stays in scope for the
+//! rest of the enclosing block, just like a `let` in plain Rust.
 
 #![feature(core, io)]
 
@@ -207,6 +248,68 @@ impl<F> ToString for Markup<F> where F: Fn(&mut fmt::Writer) -> fmt::Result {
     }
 }
 
+/// A type that knows how to render itself as HTML markup.
+///
+/// A bare `$value` splice accepts anything that implements `Render`.
+/// `render_to` has no default body. Every `Display` type implements
+/// `Render` through the blanket impl below, which escapes the value's
+/// `Display` output; that impl cannot be overridden for a `Display`
+/// type. To emit custom markup — for example a `User` that renders
+/// itself as an `<a href=...>` link — implement `Render` on a type that
+/// does not implement `Display`. The trait has no method returning a
+/// `Markup`.
+pub trait Render {
+    /// Render this value directly into `buf`.
+    ///
+    /// Implementors that emit their own trusted markup should escape any
+    /// untrusted parts themselves.
+    fn render_to(&self, buf: &mut fmt::Writer) -> fmt::Result;
+}
+
+/// Every `Display` type renders by escaping its `Display` output, which
+/// is what a bare `$value` splice did before this trait existed. Types
+/// wanting custom markup implement `Render` on a non-`Display` newtype
+/// (see `PreEscaped`) so they don't collide with this blanket impl.
+impl<T: fmt::Display> Render for T {
+    fn render_to(&self, buf: &mut fmt::Writer) -> fmt::Result {
+        use std::fmt::Writer;
+        write!(rt::Escaper { inner: buf }, "{}", self)
+    }
+}
+
+/// A wrapper that marks its contents as already-escaped markup.
+///
+/// Splicing a `PreEscaped` value with a plain `$fragment` writes the
+/// inner value verbatim, bypassing the escaper — the same effect as a
+/// `$$` prefix, but carried in the type. This lets a helper that
+/// returns `PreEscaped<String>` compose safely without every caller
+/// having to remember the `$$` sigil.
+///
+/// ```
+/// fn admin_badge() -> PreEscaped<String> {
+///     PreEscaped("<b>admin</b>".to_string())
+/// }
+/// let badge = admin_badge();
+/// html! {
+///     p { "role: " $badge }
+/// }
+/// ```
+///
+/// The `$badge` splice emits `<b>admin</b>` verbatim, because
+/// `PreEscaped`'s `Render::render_to` bypasses the escaper.
+///
+/// Note that `PreEscaped` deliberately does *not* implement `Display`:
+/// that is what keeps this `Render` impl from overlapping the blanket
+/// `impl Render for T: Display`, so the verbatim path and the escaping
+/// default can coexist without specialization.
+pub struct PreEscaped<T: fmt::Display>(pub T);
+
+impl<T: fmt::Display> Render for PreEscaped<T> {
+    fn render_to(&self, buf: &mut fmt::Writer) -> fmt::Result {
+        write!(buf, "{}", self.0)
+    }
+}
+
 /// Internal functions used by the `maud_macros` package. You should
 /// never need to call these directly.
 #[doc(hidden)]
@@ -231,21 +334,58 @@ pub mod rt {
         write!(w, "{}", value)
     }
 
+    /// Write pre-built `format_args!` into `w`, forwarding a format
+    /// spec supplied at the splice site. The `$(value; spec)` form lowers
+    /// to `write_fmt_args(w, format_args!("{:spec}", value))` — e.g.
+    /// `$(value; #x)` becomes `format_args!("{:#x}", value)` — so the
+    /// spec is baked in at expansion while the call still routes through
+    /// a plain function (rustc can't quote the `write!` invocation).
+    #[inline]
+    pub fn write_fmt_args(w: &mut fmt::Writer, args: fmt::Arguments) -> fmt::Result {
+        w.write_fmt(args)
+    }
+
+    /// Companion to `write_fmt` for the `$[expr]` splice form, which
+    /// formats with `{:?}` instead of `{}`.
+    #[inline]
+    pub fn write_fmt_debug<T: fmt::Debug>(w: &mut fmt::Writer, value: T) -> fmt::Result {
+        write!(w, "{:?}", value)
+    }
+
+    /// Render a `Render` value into `w`. Used for bare `$value` splices,
+    /// where `value` controls its own escaping via `Render::render_to`.
+    #[inline]
+    pub fn render<T: ::Render>(w: &mut fmt::Writer, value: &T) -> fmt::Result {
+        value.render_to(w)
+    }
+
     pub struct Escaper<'a, 'b: 'a> {
         pub inner: &'a mut (fmt::Writer + 'b),
     }
 
     impl<'a, 'b> fmt::Writer for Escaper<'a, 'b> {
         fn write_str(&mut self, s: &str) -> fmt::Result {
-            for c in s.chars() {
-                try!(match c {
-                    '&' => self.inner.write_str("&amp;"),
-                    '<' => self.inner.write_str("&lt;"),
-                    '>' => self.inner.write_str("&gt;"),
-                    '"' => self.inner.write_str("&quot;"),
-                    '\'' => self.inner.write_str("&#39;"),
-                    _ => write!(self.inner, "{}", c),
-                });
+            // All five escaped characters are single ASCII bytes, so we
+            // can scan by byte index and flush untouched runs as whole
+            // slices. `start` tracks the beginning of the current run.
+            let mut start = 0;
+            for (i, b) in s.bytes().enumerate() {
+                let entity = match b {
+                    b'&' => "&amp;",
+                    b'<' => "&lt;",
+                    b'>' => "&gt;",
+                    b'"' => "&quot;",
+                    b'\'' => "&#39;",
+                    _ => continue,
+                };
+                if start < i {
+                    try!(self.inner.write_str(&s[start..i]));
+                }
+                try!(self.inner.write_str(entity));
+                start = i + 1;
+            }
+            if start < s.len() {
+                try!(self.inner.write_str(&s[start..]));
             }
             Ok(())
         }
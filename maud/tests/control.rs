@@ -0,0 +1,35 @@
+#![feature(plugin)]
+#![plugin(maud_macros)]
+
+extern crate maud;
+
+#[test]
+fn for_loop() {
+    let numbers = [1, 2, 3];
+    let s = html! { ul { @for n in &numbers { li { $n } } } }.to_string();
+    assert_eq!(s, "<ul><li>1</li><li>2</li><li>3</li></ul>");
+}
+
+#[test]
+fn if_else() {
+    let admin = true;
+    let s = html! { @if admin { "yes" } @else { "no" } }.to_string();
+    assert_eq!(s, "yes");
+
+    let admin = false;
+    let s = html! { @if admin { "yes" } @else { "no" } }.to_string();
+    assert_eq!(s, "no");
+}
+
+#[test]
+fn match_expr() {
+    let x = 2;
+    let s = html! { @match x { 1 => "one", _ => "many" } }.to_string();
+    assert_eq!(s, "many");
+}
+
+#[test]
+fn let_binding() {
+    let s = html! { @let y = 1 + 1; $y }.to_string();
+    assert_eq!(s, "2");
+}
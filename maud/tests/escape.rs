@@ -0,0 +1,27 @@
+extern crate maud;
+
+use maud::escape;
+
+#[test]
+fn specials() {
+    assert_eq!(escape("<a href='/'>&\"</a>"),
+               "&lt;a href=&#39;/&#39;&gt;&amp;&quot;&lt;/a&gt;");
+}
+
+#[test]
+fn long_unescaped_run() {
+    // The common case: a long run with nothing to escape must come back
+    // unchanged (the bulk scan flushes it in a single `write_str`).
+    let input = "the quick brown fox jumps over the lazy dog";
+    assert_eq!(escape(input), input);
+}
+
+#[test]
+fn mixed_runs() {
+    assert_eq!(escape("a<b>c&d"), "a&lt;b&gt;c&amp;d");
+}
+
+#[test]
+fn adjacent_specials() {
+    assert_eq!(escape("<<>>"), "&lt;&lt;&gt;&gt;");
+}
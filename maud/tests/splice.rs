@@ -0,0 +1,25 @@
+#![feature(plugin)]
+#![plugin(maud_macros)]
+
+extern crate maud;
+
+#[test]
+fn debug_splice() {
+    let v = vec![1, 2, 3];
+    let s = html! { $[v] }.to_string();
+    assert_eq!(s, "[1, 2, 3]");
+}
+
+#[test]
+fn debug_splice_escapes() {
+    // `Debug` of `"<>"` is the quoted string `"<>"`, whose characters
+    // are then HTML-escaped.
+    let s = html! { $["<>"] }.to_string();
+    assert_eq!(s, "&quot;&lt;&gt;&quot;");
+}
+
+#[test]
+fn debug_splice_unescaped() {
+    let s = html! { $$["<>"] }.to_string();
+    assert_eq!(s, "\"<>\"");
+}
@@ -0,0 +1,47 @@
+//! Compiler plugin providing the `html!` macro for the `maud` crate.
+//!
+//! This crate is a procedural macro and is not meant to be used
+//! directly. See the `maud` crate for documentation.
+
+#![crate_type = "dylib"]
+#![feature(plugin_registrar, quote, rustc_private)]
+
+extern crate syntax;
+extern crate rustc;
+
+use rustc::plugin::Registry;
+use syntax::ast::TokenTree;
+use syntax::codemap::Span;
+use syntax::ext::base::{ExtCtxt, MacEager, MacResult};
+
+mod parse;
+mod render;
+
+fn expand_html<'cx>(cx: &'cx mut ExtCtxt, sp: Span, args: &[TokenTree])
+    -> Box<MacResult + 'cx>
+{
+    let expr = parse::parse(cx, args, sp);
+    MacEager::expr(expr)
+}
+
+#[plugin_registrar]
+pub fn plugin_registrar(reg: &mut Registry) {
+    reg.register_macro("html", expand_html);
+}
+
+/// Escape an HTML string at compile time. Mirrors `maud::escape`, but is
+/// needed here because the plugin runs before `maud` is linked.
+fn escape(s: &str) -> String {
+    let mut buf = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&#39;"),
+            _ => buf.push(c),
+        }
+    }
+    buf
+}
@@ -0,0 +1,333 @@
+use syntax::ast::{Expr, Stmt, TokenTree};
+use syntax::ast::TokenTree::{TtDelimited, TtToken};
+use syntax::codemap::Span;
+use syntax::ext::base::ExtCtxt;
+use syntax::parse::token::{self, DelimToken};
+use syntax::print::pprust;
+use syntax::ptr::P;
+
+use render::{Escape, Renderer};
+
+fn is_semi(tt: &TokenTree) -> bool {
+    if let TtToken(_, token::Semi) = *tt { true } else { false }
+}
+
+pub fn parse(cx: &mut ExtCtxt, input: &[TokenTree], sp: Span) -> P<Expr> {
+    let mut render = Renderer::new(cx);
+    Parser { input: input, pos: 0, sp: sp }.markups(&mut render);
+    render.into_expr()
+}
+
+struct Parser<'i> {
+    input: &'i [TokenTree],
+    pos: usize,
+    sp: Span,
+}
+
+impl<'i> Parser<'i> {
+    fn peek(&self) -> Option<&'i TokenTree> {
+        self.input.get(self.pos)
+    }
+
+    fn shift(&mut self, n: usize) {
+        self.pos += n;
+    }
+
+    /// Parse a run of markup nodes until the input is exhausted.
+    fn markups(&mut self, render: &mut Renderer) {
+        while self.peek().is_some() {
+            self.markup(render);
+        }
+    }
+
+    /// Parse a single markup node.
+    fn markup(&mut self, render: &mut Renderer) {
+        match self.peek() {
+            Some(&TtToken(_, token::Literal(token::Lit::Str_(s), _))) => {
+                self.shift(1);
+                render.string(&token::get_name(s), Escape::Escape);
+            }
+            Some(&TtToken(_, token::Dollar)) => self.splice(render),
+            Some(&TtToken(_, token::At)) => self.control(render),
+            Some(&TtToken(_, token::Ident(..))) => self.element(render),
+            Some(_) => self.error("expected markup"),
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    /// Parse a splice, starting at a `$`. A second `$` passes through
+    /// unescaped.
+    fn splice(&mut self, render: &mut Renderer) {
+        self.shift(1);
+        let escape = match self.peek() {
+            Some(&TtToken(_, token::Dollar)) => { self.shift(1); Escape::PassThrough }
+            _ => Escape::Escape,
+        };
+        match self.peek() {
+            Some(&TtDelimited(_, ref d)) if d.delim == DelimToken::Bracket => {
+                let expr = render.parse_tts(&d.tts);
+                self.shift(1);
+                render.splice_debug(expr, escape);
+            }
+            Some(&TtDelimited(_, ref d)) if d.delim == DelimToken::Paren => {
+                // `$(expr)` or `$(expr; spec)`.
+                match d.tts.iter().position(is_semi) {
+                    Some(i) => {
+                        let expr = render.parse_tts(&d.tts[..i]);
+                        // Concatenate the spec tokens individually:
+                        // `tts_to_string` inserts spaces between e.g. `#`
+                        // and `x`, which would corrupt `{:#x}` / `{:.2}`.
+                        let spec: String = d.tts[i + 1..].iter()
+                            .map(|tt| pprust::tt_to_string(tt))
+                            .collect();
+                        self.shift(1);
+                        render.splice_spec(expr, &spec, escape);
+                    }
+                    None => {
+                        let expr = render.parse_tts(&d.tts);
+                        self.shift(1);
+                        render.splice(expr, escape);
+                    }
+                }
+            }
+            _ => {
+                let tts = self.bare_splice();
+                let expr = render.parse_tts(tts);
+                render.splice(expr, escape);
+            }
+        }
+    }
+
+    /// Collect the token run making up a bracket-free splice: an initial
+    /// identifier followed by any chain of `.ident`, `(...)`, or `[...]`.
+    fn bare_splice(&mut self) -> &'i [TokenTree] {
+        let start = self.pos;
+        self.shift(1);
+        loop {
+            match self.peek() {
+                Some(&TtToken(_, token::Dot)) => self.shift(2),
+                Some(&TtDelimited(_, ref d))
+                        if d.delim == DelimToken::Paren
+                        || d.delim == DelimToken::Bracket => self.shift(1),
+                _ => break,
+            }
+        }
+        &self.input[start..self.pos]
+    }
+
+    /// Parse an element: `tag attr="v" { body }`, `tag "child"`, or
+    /// `tag /` for a void element.
+    fn element(&mut self, render: &mut Renderer) {
+        let name = match self.peek() {
+            Some(&TtToken(_, token::Ident(id, _))) => {
+                self.shift(1);
+                token::get_ident(id).to_string()
+            }
+            _ => return self.error("expected element name"),
+        };
+        render.string(&format!("<{}", name), Escape::PassThrough);
+        self.attributes(render);
+        if let Some(&TtToken(_, token::BinOp(token::Slash))) = self.peek() {
+            self.shift(1);
+            render.string(">", Escape::PassThrough);
+            return;
+        }
+        render.string(">", Escape::PassThrough);
+        self.body(render);
+        render.string(&format!("</{}>", name), Escape::PassThrough);
+    }
+
+    fn attributes(&mut self, render: &mut Renderer) {
+        while let Some(&TtToken(_, token::Ident(id, _))) = self.peek() {
+            self.shift(1);
+            let name = token::get_ident(id).to_string();
+            match self.peek() {
+                Some(&TtToken(_, token::Question)) => {
+                    self.shift(1);
+                    render.string(&format!(" {}", name), Escape::PassThrough);
+                }
+                Some(&TtToken(_, token::Eq)) => {
+                    self.shift(1);
+                    render.string(&format!(" {}=\"", name), Escape::PassThrough);
+                    self.markup(render);
+                    render.string("\"", Escape::PassThrough);
+                }
+                _ => return self.error("expected `=` or `?` after attribute name"),
+            }
+        }
+    }
+
+    /// Parse an element body: either a `{ ... }` block or a single node.
+    fn body(&mut self, render: &mut Renderer) {
+        match self.peek() {
+            Some(&TtDelimited(_, ref d)) if d.delim == DelimToken::Brace => {
+                self.shift(1);
+                Parser { input: &d.tts, pos: 0, sp: self.sp }.markups(render);
+            }
+            _ => self.markup(render),
+        }
+    }
+
+    /// Parse an `@`-prefixed control-flow construct.
+    fn control(&mut self, render: &mut Renderer) {
+        self.shift(1);
+        match self.ident() {
+            Some(ref kw) if kw == "if" => self.control_if(render),
+            Some(ref kw) if kw == "for" => self.control_for(render),
+            Some(ref kw) if kw == "match" => self.control_match(render),
+            Some(ref kw) if kw == "let" => self.control_let(render),
+            Some(kw) => self.error(&format!("unknown control-flow keyword `@{}`", kw)),
+            None => self.error("expected a control-flow keyword after `@`"),
+        }
+    }
+
+    fn control_if(&mut self, render: &mut Renderer) {
+        let cond = { let tts = self.take_until_brace(); render.parse_tts(tts) };
+        let then = self.take_block(render);
+        let els = if self.eat_at_keyword("else") {
+            // `@else @if ...` (else-if) or `@else { ... }`.
+            Some(if let Some(&TtToken(_, token::At)) = self.peek() {
+                self.block_of(render, |p, r| p.control(r))
+            } else {
+                self.take_block(render)
+            })
+        } else {
+            None
+        };
+        render.emit_if(cond, then, els);
+    }
+
+    fn control_for(&mut self, render: &mut Renderer) {
+        let pat = { let tts = self.take_until_keyword("in"); render.parse_pat(tts) };
+        self.shift(1); // the `in` keyword
+        let iter = { let tts = self.take_until_brace(); render.parse_tts(tts) };
+        let body = self.take_block(render);
+        render.emit_for(pat, iter, body);
+    }
+
+    fn control_match(&mut self, render: &mut Renderer) {
+        let scrutinee = { let tts = self.take_until_brace(); render.parse_tts(tts) };
+        let arm_tts = match self.peek() {
+            Some(&TtDelimited(_, ref d)) if d.delim == DelimToken::Brace => {
+                self.shift(1);
+                &d.tts[..]
+            }
+            _ => return self.error("expected `{` after `@match` scrutinee"),
+        };
+        let mut arms = vec![];
+        let mut p = Parser { input: arm_tts, pos: 0, sp: self.sp };
+        while p.peek().is_some() {
+            let pat = { let tts = p.take_until_fat_arrow(); render.parse_pat(tts) };
+            p.shift(1); // the `=>`
+            let body = p.take_arm_body(render);
+            if let Some(&TtToken(_, token::Comma)) = p.peek() {
+                p.shift(1);
+            }
+            arms.push((pat, body));
+        }
+        render.emit_match(scrutinee, arms);
+    }
+
+    fn control_let(&mut self, render: &mut Renderer) {
+        let pat = { let tts = self.take_until_token(&token::Eq); render.parse_pat(tts) };
+        self.shift(1); // the `=`
+        let expr = { let tts = self.take_until_token(&token::Semi); render.parse_tts(tts) };
+        self.shift(1); // the `;`
+        render.emit_let(pat, expr);
+    }
+
+    /// A `@match` arm body: a `{ ... }` block, or a single node up to the
+    /// next comma.
+    fn take_arm_body(&mut self, render: &mut Renderer) -> Vec<P<Stmt>> {
+        match self.peek() {
+            Some(&TtDelimited(_, _)) => self.take_block(render),
+            _ => self.block_of(render, |p, r| p.markup(r)),
+        }
+    }
+
+    // --- token-run helpers -------------------------------------------------
+
+    fn ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(&TtToken(_, token::Ident(id, _))) => {
+                self.shift(1);
+                Some(token::get_ident(id).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Consume `@kw` if present, returning whether it matched.
+    fn eat_at_keyword(&mut self, kw: &str) -> bool {
+        if let Some(&TtToken(_, token::At)) = self.peek() {
+            if let Some(&TtToken(_, token::Ident(id, _))) = self.input.get(self.pos + 1) {
+                if token::get_ident(id) == kw {
+                    self.shift(2);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn take_until_brace(&mut self) -> &'i [TokenTree] {
+        let start = self.pos;
+        while let Some(tt) = self.peek() {
+            if let TtDelimited(_, ref d) = *tt {
+                if d.delim == DelimToken::Brace { break; }
+            }
+            self.shift(1);
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn take_until_token(&mut self, tok: &token::Token) -> &'i [TokenTree] {
+        let start = self.pos;
+        while let Some(tt) = self.peek() {
+            if let TtToken(_, ref t) = *tt {
+                if t == tok { break; }
+            }
+            self.shift(1);
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn take_until_fat_arrow(&mut self) -> &'i [TokenTree] {
+        self.take_until_token(&token::FatArrow)
+    }
+
+    fn take_until_keyword(&mut self, kw: &str) -> &'i [TokenTree] {
+        let start = self.pos;
+        while let Some(tt) = self.peek() {
+            if let TtToken(_, token::Ident(id, _)) = *tt {
+                if token::get_ident(id) == kw { break; }
+            }
+            self.shift(1);
+        }
+        &self.input[start..self.pos]
+    }
+
+    /// Expect a `{ ... }` block and render its markup into a fresh
+    /// statement list.
+    fn take_block(&mut self, render: &mut Renderer) -> Vec<P<Stmt>> {
+        match self.peek() {
+            Some(&TtDelimited(_, ref d)) if d.delim == DelimToken::Brace => {
+                let tts = &d.tts[..];
+                self.shift(1);
+                render.block(|r| Parser { input: tts, pos: 0, sp: r.cx.call_site() }.markups(r))
+            }
+            _ => self.error("expected a `{ ... }` block"),
+        }
+    }
+
+    /// Render a sub-parse (driven by `f`) into a fresh statement list.
+    fn block_of<F>(&mut self, render: &mut Renderer, f: F) -> Vec<P<Stmt>>
+        where F: FnOnce(&mut Parser<'i>, &mut Renderer)
+    {
+        render.block(|r| f(self, r))
+    }
+
+    fn error(&self, msg: &str) -> ! {
+        panic!("maud: {}", msg)
+    }
+}
@@ -0,0 +1,168 @@
+use syntax::ast::{Expr, Ident, Pat, Stmt, TokenTree};
+use syntax::ext::base::ExtCtxt;
+use syntax::ext::build::AstBuilder;
+use syntax::parse::token;
+use syntax::ptr::P;
+
+#[derive(Copy, PartialEq)]
+pub enum Escape {
+    PassThrough,
+    Escape,
+}
+
+/// Collects the statements that make up the body of the closure passed
+/// to `maud::rt::make_markup`. Literals, elements and splices lower to
+/// `try!(...)` statements writing into the `w` parameter; control-flow
+/// nodes lower to the matching Rust `if`/`for`/`match`/`let` statements.
+pub struct Renderer<'cx, 'a: 'cx> {
+    pub cx: &'cx mut ExtCtxt<'a>,
+    w: Ident,
+    stmts: Vec<P<Stmt>>,
+}
+
+impl<'cx, 'a> Renderer<'cx, 'a> {
+    pub fn new(cx: &'cx mut ExtCtxt<'a>) -> Renderer<'cx, 'a> {
+        let w = token::str_to_ident("w");
+        Renderer { cx: cx, w: w, stmts: vec![] }
+    }
+
+    /// Parse a token run into an expression, using this renderer's
+    /// `ExtCtxt`. Splices reuse Rust's own expression grammar this way.
+    pub fn parse_tts(&mut self, tts: &[TokenTree]) -> P<Expr> {
+        self.cx.new_parser_from_tts(tts).parse_expr()
+    }
+
+    /// Parse a token run into a pattern, for control-flow heads.
+    pub fn parse_pat(&mut self, tts: &[TokenTree]) -> P<Pat> {
+        self.cx.new_parser_from_tts(tts).parse_pat()
+    }
+
+    /// Render a nested block (e.g. a control-flow body) with a fresh
+    /// statement list but the same writer, returning its statements.
+    pub fn block<F>(&mut self, f: F) -> Vec<P<Stmt>>
+        where F: FnOnce(&mut Renderer<'cx, 'a>)
+    {
+        let saved = ::std::mem::replace(&mut self.stmts, vec![]);
+        f(self);
+        ::std::mem::replace(&mut self.stmts, saved)
+    }
+
+    pub fn push_stmt(&mut self, stmt: P<Stmt>) {
+        self.stmts.push(stmt);
+    }
+
+    fn writer(&self) -> P<Expr> {
+        let w = self.w;
+        quote_expr!(self.cx, $w)
+    }
+
+    /// Emit a static string, HTML-escaping it at compile time unless
+    /// `escape` is `PassThrough`.
+    pub fn string(&mut self, s: &str, escape: Escape) {
+        let escaped;
+        let s = match escape {
+            Escape::PassThrough => s,
+            Escape::Escape => { escaped = super::escape(s); &*escaped },
+        };
+        let w = self.writer();
+        let stmt = quote_stmt!(self.cx, try!($w.write_str($s));).unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Emit a `Render` splice (`$value`). The value controls its own
+    /// escaping through `Render::render_to`; a `$$value` instead writes
+    /// the raw `Display` output.
+    pub fn splice(&mut self, expr: P<Expr>, escape: Escape) {
+        let w = self.writer();
+        let stmt = match escape {
+            Escape::Escape =>
+                quote_stmt!(self.cx, try!(::maud::rt::render($w, &$expr));),
+            Escape::PassThrough =>
+                quote_stmt!(self.cx, try!(::maud::rt::write_fmt($w, &$expr));),
+        }.unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Emit a Debug-formatted splice (`$[expr]` / `$$[expr]`).
+    pub fn splice_debug(&mut self, expr: P<Expr>, escape: Escape) {
+        let w = self.writer();
+        let stmt = match escape {
+            Escape::Escape => quote_stmt!(self.cx,
+                try!(::maud::rt::write_fmt_debug(
+                        &mut ::maud::rt::Escaper { inner: $w }, &$expr));),
+            Escape::PassThrough =>
+                quote_stmt!(self.cx, try!(::maud::rt::write_fmt_debug($w, &$expr));),
+        }.unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Emit a splice with an explicit format spec (`$(expr; spec)`),
+    /// lowering to `format_args!("{:spec}", expr)`.
+    pub fn splice_spec(&mut self, expr: P<Expr>, spec: &str, escape: Escape) {
+        let fmt = format!("{{:{}}}", spec);
+        let fmt = self.cx.expr_str(self.cx.call_site(),
+                                   token::intern_and_get_ident(&fmt));
+        let w = self.writer();
+        let stmt = match escape {
+            Escape::Escape => quote_stmt!(self.cx,
+                try!(::maud::rt::write_fmt_args(
+                        &mut ::maud::rt::Escaper { inner: $w },
+                        format_args!($fmt, $expr)));),
+            Escape::PassThrough => quote_stmt!(self.cx,
+                try!(::maud::rt::write_fmt_args($w, format_args!($fmt, $expr)));),
+        }.unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Emit an `@if` / `@else` construct around the rendered bodies.
+    pub fn emit_if(&mut self, cond: P<Expr>, then: Vec<P<Stmt>>,
+                   els: Option<Vec<P<Stmt>>>) {
+        let sp = self.cx.call_site();
+        let then = self.cx.block(sp, then, None);
+        let stmt = match els {
+            Some(els) => {
+                let els = self.cx.block(sp, els, None);
+                quote_stmt!(self.cx, if $cond $then else $els)
+            }
+            None => quote_stmt!(self.cx, if $cond $then),
+        }.unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Emit a `@for pat in iter { body }` loop.
+    pub fn emit_for(&mut self, pat: P<Pat>, iter: P<Expr>, body: Vec<P<Stmt>>) {
+        let body = self.cx.block(self.cx.call_site(), body, None);
+        let stmt = quote_stmt!(self.cx, for $pat in $iter $body).unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Emit a `@match scrutinee { arms }` expression.
+    pub fn emit_match(&mut self, scrutinee: P<Expr>,
+                      arms: Vec<(P<Pat>, Vec<P<Stmt>>)>) {
+        let sp = self.cx.call_site();
+        let arms = arms.into_iter().map(|(pat, body)| {
+            let block = self.cx.block(sp, body, None);
+            let body = self.cx.expr_block(block);
+            self.cx.arm(sp, vec![pat], body)
+        }).collect();
+        let expr = self.cx.expr_match(sp, scrutinee, arms);
+        let stmt = self.cx.stmt_expr(expr);
+        self.push_stmt(stmt);
+    }
+
+    /// Emit a `@let pat = expr;` binding.
+    pub fn emit_let(&mut self, pat: P<Pat>, expr: P<Expr>) {
+        let stmt = quote_stmt!(self.cx, let $pat = $expr;).unwrap();
+        self.push_stmt(stmt);
+    }
+
+    /// Collect the accumulated statements into the closure expression
+    /// that the `html!` macro ultimately expands to.
+    pub fn into_expr(self) -> P<Expr> {
+        let Renderer { cx, w, stmts } = self;
+        let block = cx.block(cx.call_site(), stmts, Some(quote_expr!(cx, Ok(()))));
+        quote_expr!(cx,
+            ::maud::rt::make_markup(
+                move |$w: &mut ::std::fmt::Writer| -> ::std::fmt::Result $block))
+    }
+}